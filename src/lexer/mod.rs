@@ -1,27 +1,27 @@
 use thiserror::Error;
 
-use crate::token::{Location, Token, TokenType};
+use crate::token::{Location, Span, Token, TokenType};
 
 #[cfg(test)]
 mod tests;
 
+/// A cursor over `input`, tracked as a byte offset (matching
+/// `str`'s own indexing) with the current `Location` maintained incrementally
+/// instead of being rescanned from the start on every lookup.
 #[derive(Debug, Clone)]
 pub struct Input {
     pub input: String,
     pub cursor: u32,
+    location: Location,
 }
 
 impl Input {
     pub fn of(input: String) -> Self {
-        Self { input, cursor: 0 }
-    }
-
-    pub fn move_cursor(&mut self, chars: u32) {
-        if self.cursor + chars > self.input.len() as u32 {
-            return;
+        Self {
+            input,
+            cursor: 0,
+            location: Location { line: 1, col: 1 },
         }
-
-        self.cursor += chars;
     }
 
     pub fn remaining_length(&self) -> u32 {
@@ -29,13 +29,26 @@ impl Input {
     }
 
     pub fn peek(&self) -> Option<char> {
-        self.input.chars().nth(self.cursor as usize)
+        self.input[self.cursor as usize..].chars().next()
     }
 
     pub fn has_remaining_input(&self) -> bool {
         self.cursor < self.input.len() as u32
     }
 
+    /// Rewinds the cursor by exactly one previously-read, non-newline char.
+    /// This is the checkpoint/rewind the lexer leans on when "fixing" the
+    /// column on dead code, so it only ever needs to undo the single char
+    /// it just consumed.
+    pub fn rewind_one(&mut self) {
+        let Some(c) = self.input[..self.cursor as usize].chars().next_back() else {
+            return;
+        };
+
+        self.cursor -= c.len_utf8() as u32;
+        self.location.col = self.location.col.saturating_sub(1);
+    }
+
     pub fn skip_whitespace(&mut self, max_spaces: u32, preserve_single: bool) {
         if preserve_single && self.remaining_length() == 1 && self.peek() == Some(' ') {
             return;
@@ -56,48 +69,57 @@ impl Input {
     }
 
     pub fn peek_string_chars(&self, chars: u32) -> String {
-        let remaining = self.remaining_input();
-        if chars > remaining.len() as u32 {
-            return "".to_string();
-        }
-
-        remaining[0..chars as usize].to_string()
+        self.input[self.cursor as usize..]
+            .chars()
+            .take(chars as usize)
+            .collect()
     }
 
     pub fn read(&mut self, chars: u32) -> String {
-        let read_string = self.peek_string_chars(chars);
-        self.move_cursor(chars);
-        read_string
-    }
+        let mut read_string = String::new();
 
-    pub fn current_location(&self) -> Location {
-        let up_to_cursor = &self.input[..self.cursor as usize];
-        let mut line = 1;
-        let mut col = 1;
+        for _ in 0..chars {
+            let Some(c) = self.peek() else { break };
+
+            self.cursor += c.len_utf8() as u32;
 
-        for c in up_to_cursor.chars() {
             if c == '\n' {
-                line += 1;
-                col = 1;
+                self.location.line += 1;
+                self.location.col = 1;
             } else {
-                col += 1;
+                self.location.col += 1;
             }
+
+            read_string.push(c);
         }
 
-        Location { line, col }
+        read_string
+    }
+
+    pub fn current_location(&self) -> Location {
+        self.location
     }
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum LexError {
     #[error("unterminated string at {0}")]
-    UnterminatedString(Location),
+    UnterminatedString(Span),
 
     #[error("unterminated line at {0}")]
-    UnterminatedLine(Location),
+    UnterminatedLine(Span),
 
     #[error("dead code at {0}")]
-    DeadCode(Location),
+    DeadCode(Span),
+
+    #[error("malformed escape sequence at {0}")]
+    MalformedEscape(Span),
+
+    #[error("malformed char literal at {0}")]
+    MalformedChar(Span),
+
+    #[error("malformed number at {0}")]
+    MalformedNumber(Span),
 }
 
 impl LexError {
@@ -106,6 +128,22 @@ impl LexError {
             Self::UnterminatedString(_) => "UnterminatedString".to_string(),
             Self::UnterminatedLine(_) => "UnterminatedLine".to_string(),
             Self::DeadCode(_) => "DeadCode".to_string(),
+            Self::MalformedEscape(_) => "MalformedEscape".to_string(),
+            Self::MalformedChar(_) => "MalformedChar".to_string(),
+            Self::MalformedNumber(_) => "MalformedNumber".to_string(),
+        }
+    }
+
+    /// The full offending span, so the companion `Error` token pushed
+    /// alongside this error can point at exactly the same range.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::UnterminatedString(span)
+            | Self::UnterminatedLine(span)
+            | Self::DeadCode(span)
+            | Self::MalformedEscape(span)
+            | Self::MalformedChar(span)
+            | Self::MalformedNumber(span) => *span,
         }
     }
 }
@@ -113,6 +151,20 @@ impl LexError {
 pub struct Lexer {
     pub input: Input,
     pub tokens: Vec<Token>,
+    pub errors: Vec<LexError>,
+    /// Index of the next not-yet-handed-out token in `tokens`, so
+    /// `next_token` can pull tokens one at a time without draining the
+    /// backing `Vec`.
+    next_index: usize,
+    /// Index of the next not-yet-handed-out error in `errors`, advanced in
+    /// lockstep with `next_index` whenever that token is the companion
+    /// `Error` token for it, so `next_token` can hand the real `LexError`
+    /// back as `Err` instead of the placeholder token.
+    next_error_index: usize,
+    /// Set once lexing has reached a point it will never produce further
+    /// tokens from (an `!`/`?` marker, or unrecoverable end of input), so
+    /// `next_token` can report `Ok(None)` without rescanning.
+    done: bool,
 }
 
 impl Lexer {
@@ -120,18 +172,37 @@ impl Lexer {
         Lexer {
             input,
             tokens: Vec::new(),
+            errors: Vec::new(),
+            next_index: 0,
+            next_error_index: 0,
+            done: false,
         }
     }
 
-    pub fn add_token(&mut self, token: TokenType, value: String) {
+    pub fn add_token(&mut self, token: TokenType, value: String, start: Location) {
         self.tokens.push(Token {
             ty: token,
             value,
-            location: self.input.current_location(),
+            span: Span {
+                start,
+                end: self.input.current_location(),
+            },
         })
     }
 
-    fn lex_identifier(&mut self) {
+    /// Pushes the companion `Error` token for a `LexError`, spanning exactly
+    /// the range the error itself carries.
+    fn add_error_token(&mut self, err: &LexError) {
+        let span = err.span();
+
+        self.tokens.push(Token {
+            ty: TokenType::Error,
+            value: err.name(),
+            span,
+        });
+    }
+
+    fn lex_identifier(&mut self, start: Location) {
         let mut ident = self.input.read(1);
 
         while self.input.has_remaining_input() && self.input.peek().unwrap().is_alphanumeric() {
@@ -140,129 +211,442 @@ impl Lexer {
 
         match &*ident {
             "f" | "fu" | "fun" | "func" | "funct" | "functi" | "functio" | "function" => {
-                self.add_token(TokenType::FunctionDeclaration, ident)
+                self.add_token(TokenType::FunctionDeclaration, ident, start)
             }
 
             "Int" | "String" | "Char" | "Digit" | "Bool" => {
-                self.add_token(TokenType::Primitive, ident)
+                self.add_token(TokenType::Primitive, ident, start)
             }
 
-            "true" | "false" | "maybe" => self.add_token(TokenType::BoolLiteral, ident),
+            "true" | "false" | "maybe" => self.add_token(TokenType::BoolLiteral, ident, start),
+
+            "return" => self.add_token(TokenType::Return, ident, start),
+
+            _ => self.add_token(TokenType::Identifier, ident, start),
+        }
+    }
+
+    /// Decodes a single backslash escape, with the cursor positioned right
+    /// after the `\`. Supports `\n`, `\t`, `\r`, `\\`, `\"`, `\'` and the
+    /// unicode escape `\u{..}`.
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        let start = self.input.current_location();
+
+        let Some(escaped) = self.input.peek() else {
+            return Err(LexError::MalformedEscape(Span { start, end: start }));
+        };
+
+        self.input.read(1);
+
+        let malformed = |this: &Self| {
+            LexError::MalformedEscape(Span {
+                start,
+                end: this.input.current_location(),
+            })
+        };
+
+        match escaped {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            'u' => {
+                if self.input.peek() != Some('{') {
+                    return Err(malformed(self));
+                }
+                self.input.read(1);
 
-            "return" => self.add_token(TokenType::Return, ident),
+                let mut hex = String::new();
+                while self.input.has_remaining_input() && self.input.peek() != Some('}') {
+                    hex.push_str(&self.input.read(1));
+                }
 
-            _ => self.add_token(TokenType::Identifier, ident),
+                if self.input.peek() != Some('}') {
+                    return Err(malformed(self));
+                }
+                self.input.read(1);
+
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| malformed(self))
+            }
+            _ => Err(malformed(self)),
         }
+    }
 
-        self.input.cursor -= 1;
+    /// Skips ahead to (and past) the next newline, used after a dead-code
+    /// error so the rest of that line is consumed instead of returning
+    /// early; lexing does not resume after it.
+    fn recover_to_next_line(&mut self) {
+        while self.input.has_remaining_input() && self.input.peek() != Some('\n') {
+            self.input.read(1);
+        }
+
+        if self.input.has_remaining_input() {
+            self.input.read(1);
+        }
     }
 
-    pub fn lex(mut self) -> Result<Vec<Token>, LexError> {
-        while self.input.has_remaining_input() {
-            self.input.skip_whitespace(u32::MAX, true);
+    /// Skips ahead past a malformed char literal, consuming up to and
+    /// including the next `'` (or stopping at the next newline if there
+    /// isn't one), so a dangling closing quote doesn't get mistaken for
+    /// the start of another char literal.
+    fn recover_char_literal(&mut self) {
+        while self.input.has_remaining_input()
+            && !matches!(self.input.peek(), Some('\'') | Some('\n'))
+        {
+            self.input.read(1);
+        }
+
+        if self.input.peek() == Some('\'') {
+            self.input.read(1);
+        }
+    }
 
-            let Some(current) = self.input.peek() else {
-                break;
-            };
+    /// Consumes a run of one or more stray `.` chars that make a number
+    /// malformed (a trailing dot with no fraction digits, or an extra dot
+    /// after a fraction already has one), returning the span they cover so
+    /// none of them are left dangling for the next lex_step to misread.
+    fn consume_malformed_number_dots(&mut self) -> Span {
+        let start = self.input.current_location();
 
-            match current {
-                ' ' => {}
-                '"' => {
-                    let mut str = String::new();
+        while self.input.peek() == Some('.') {
+            self.input.read(1);
+        }
 
-                    self.input.read(1);
+        Span {
+            start,
+            end: self.input.current_location(),
+        }
+    }
 
-                    while self.input.has_remaining_input() && self.input.peek().unwrap() != '"' {
-                        str.push_str(&self.input.read(1));
-                    }
+    /// Marks the lexer as having nothing left to produce. Also covers the
+    /// "file didn't end with an eol (debug) marker" check, since that used
+    /// to run once the old all-at-once loop terminated for any reason.
+    fn mark_done(&mut self) {
+        self.done = true;
 
-                    if !self.input.has_remaining_input() {
-                        return Err(LexError::UnterminatedString(self.input.current_location()));
-                    }
+        if let Some(last_token) = self.tokens.iter().next_back()
+            && !(last_token.ty == TokenType::Eol || last_token.ty == TokenType::EolDebug)
+        {
+            let end = self.input.current_location();
+            let err = LexError::UnterminatedLine(Span { start: end, end });
+            self.add_error_token(&err);
+            self.errors.push(err);
+        }
+    }
 
-                    self.add_token(TokenType::StringLiteral, str);
-                }
-                ',' => self.add_token(TokenType::Comma, ",".to_string()),
-                '!' => {
-                    let mut eol = self.input.read(1);
-                    self.input.skip_whitespace(u32::MAX, false);
+    /// Lexes the tokens produced by a single position in the input (usually
+    /// one, but e.g. a recovered error also emits an `Error` token), pushing
+    /// them onto `self.tokens`. Marks the lexer `done` once it reaches a
+    /// point lexing should never resume from.
+    fn lex_step(&mut self) {
+        self.input.skip_whitespace(u32::MAX, true);
 
-                    while self.input.has_remaining_input() {
-                        let current = self.input.read(1);
+        let start = self.input.current_location();
 
-                        if current != "!" && current != "\n" {
-                            self.input.cursor -= 1; // fix the column exceeding length
-                            return Err(LexError::DeadCode(self.input.current_location()));
-                        } else {
-                            eol.push_str(&current);
+        let Some(current) = self.input.peek() else {
+            self.mark_done();
+            return;
+        };
+
+        match current {
+            ' ' => {
+                self.input.read(1);
+            }
+            '"' => {
+                let mut str = String::new();
+
+                self.input.read(1);
+
+                while self.input.has_remaining_input()
+                    && !matches!(self.input.peek(), Some('"') | Some('\n'))
+                {
+                    let c = self.input.read(1).chars().next().unwrap();
+
+                    if c == '\\' {
+                        match self.read_escape() {
+                            Ok(decoded) => str.push(decoded),
+                            Err(err) => {
+                                self.add_error_token(&err);
+                                self.errors.push(err);
+                            }
                         }
+                    } else {
+                        str.push(c);
                     }
-
-                    self.add_token(TokenType::Eol, eol);
-                    break;
                 }
-                '?' => {
-                    let eol_debug = self.input.read(1);
-                    self.input.skip_whitespace(u32::MAX, false);
 
-                    if self.input.has_remaining_input() {
-                        let current = self.input.read(1);
+                if self.input.peek() != Some('"') {
+                    // Hit a newline or the end of input before the closing
+                    // quote. Report what we have and, if there's a next
+                    // line to try, keep lexing from there instead of
+                    // treating the rest of the file as dead.
+                    let end = self.input.current_location();
+                    let err = LexError::UnterminatedString(Span { start, end });
+                    self.add_token(TokenType::StringLiteral, str, start);
+                    self.add_error_token(&err);
+                    self.errors.push(err);
 
-                        if current != "\n" {
-                            self.input.cursor -= 1; // fix the column exceeding length
-                            return Err(LexError::DeadCode(self.input.current_location()));
-                        }
+                    if self.input.has_remaining_input() {
+                        self.input.read(1); // consume the '\n', resume on the next line
+                    } else {
+                        self.mark_done();
                     }
 
-                    self.add_token(TokenType::EolDebug, eol_debug);
-                    break;
+                    return;
                 }
-                '(' => self.add_token(TokenType::Lparen, "(".to_string()),
-                ')' => self.add_token(TokenType::Rparen, ")".to_string()),
-                '{' => self.add_token(TokenType::Lbrace, "{".to_string()),
-                '}' => self.add_token(TokenType::Rbrace, "}".to_string()),
-                ';' => self.add_token(TokenType::Not, ";".to_string()),
-                '=' => {
-                    if self.input.peek().unwrap_or_default() == '>' {
+
+                self.input.read(1); // consume the closing quote
+                self.add_token(TokenType::StringLiteral, str, start);
+            }
+            '\'' => {
+                self.input.read(1);
+
+                let value = match self.input.peek() {
+                    Some('\\') => {
+                        self.input.read(1);
+                        self.read_escape()
+                    }
+                    Some(c) if c != '\'' && c != '\n' => {
                         self.input.read(1);
-                        self.add_token(TokenType::Arrow, "=>".to_string());
+                        Ok(c)
+                    }
+                    _ => {
+                        let end = self.input.current_location();
+                        Err(LexError::MalformedChar(Span { start, end }))
+                    }
+                };
+
+                match value {
+                    Ok(c) if self.input.peek() == Some('\'') => {
+                        self.input.read(1); // consume the closing quote
+                        self.add_token(TokenType::CharLiteral, c.to_string(), start);
+                    }
+                    Ok(_) => {
+                        let end = self.input.current_location();
+                        let err = LexError::MalformedChar(Span { start, end });
+                        self.add_error_token(&err);
+                        self.errors.push(err);
+                        self.recover_char_literal();
+                    }
+                    Err(err) => {
+                        self.add_error_token(&err);
+                        self.errors.push(err);
+                        self.recover_char_literal();
+                    }
+                }
+            }
+            ',' => {
+                self.input.read(1);
+                self.add_token(TokenType::Comma, ",".to_string(), start);
+            }
+            '!' => {
+                let mut eol = self.input.read(1);
+                self.input.skip_whitespace(u32::MAX, false);
+
+                while self.input.has_remaining_input() {
+                    let current = self.input.read(1);
+
+                    if current != "!" && current != "\n" {
+                        let bad_end = self.input.current_location();
+                        self.input.rewind_one(); // fix the column exceeding length
+                        let bad_start = self.input.current_location();
+
+                        // The Eol's own span ends right here, before any
+                        // recovery moves the cursor into the dead-code
+                        // range that follows it.
+                        self.add_token(TokenType::Eol, eol, start);
+
+                        let err = LexError::DeadCode(Span {
+                            start: bad_start,
+                            end: bad_end,
+                        });
+                        self.add_error_token(&err);
+                        self.errors.push(err);
+                        self.recover_to_next_line();
+                        // The line did end in a marker, so mark_done's
+                        // missing-marker check does not apply here.
+                        self.done = true;
+                        return;
                     } else {
-                        self.add_token(TokenType::Equals, "=".to_string());
+                        eol.push_str(&current);
                     }
                 }
-                _ => {
-                    if current.is_numeric() {
-                        let mut number = String::new();
-
-                        while self.input.has_remaining_input()
-                            && self.input.peek().unwrap().is_numeric()
-                        {
-                            number.push_str(&self.input.read(1));
-                        }
 
-                        self.add_token(TokenType::IntLiteral, number);
+                self.add_token(TokenType::Eol, eol, start);
+                self.mark_done();
+            }
+            '?' => {
+                let eol_debug = self.input.read(1);
+                self.input.skip_whitespace(u32::MAX, false);
+
+                if self.input.has_remaining_input() {
+                    let current = self.input.read(1);
+
+                    if current != "\n" {
+                        let bad_end = self.input.current_location();
+                        self.input.rewind_one(); // fix the column exceeding length
+                        let bad_start = self.input.current_location();
+
+                        // The EolDebug's own span ends right here, before
+                        // any recovery moves the cursor into the
+                        // dead-code range that follows it.
+                        self.add_token(TokenType::EolDebug, eol_debug, start);
+
+                        let err = LexError::DeadCode(Span {
+                            start: bad_start,
+                            end: bad_end,
+                        });
+                        self.add_error_token(&err);
+                        self.errors.push(err);
+                        self.recover_to_next_line();
+                        // The line did end in a marker, so mark_done's
+                        // missing-marker check does not apply here.
+                        self.done = true;
+                        return;
                     }
+                }
 
-                    if current.is_alphabetic() {
-                        self.lex_identifier();
+                self.add_token(TokenType::EolDebug, eol_debug, start);
+                self.mark_done();
+            }
+            '(' => {
+                self.input.read(1);
+                self.add_token(TokenType::Lparen, "(".to_string(), start);
+            }
+            ')' => {
+                self.input.read(1);
+                self.add_token(TokenType::Rparen, ")".to_string(), start);
+            }
+            '{' => {
+                self.input.read(1);
+                self.add_token(TokenType::Lbrace, "{".to_string(), start);
+            }
+            '}' => {
+                self.input.read(1);
+                self.add_token(TokenType::Rbrace, "}".to_string(), start);
+            }
+            ';' => {
+                self.input.read(1);
+                self.add_token(TokenType::Not, ";".to_string(), start);
+            }
+            '=' => {
+                self.input.read(1);
+
+                if self.input.peek() == Some('>') {
+                    self.input.read(1);
+                    self.add_token(TokenType::Arrow, "=>".to_string(), start);
+                } else {
+                    self.add_token(TokenType::Equals, "=".to_string(), start);
+                }
+            }
+            _ => {
+                if current.is_numeric() {
+                    let mut number = String::new();
+                    let mut ty = TokenType::IntLiteral;
+
+                    while self.input.has_remaining_input() && self.input.peek().unwrap().is_numeric()
+                    {
+                        number.push_str(&self.input.read(1));
+                    }
+
+                    let mut has_trailing_dot = false;
+
+                    if self.input.peek() == Some('.') {
+                        let next_is_digit = self
+                            .input
+                            .peek_string_chars(2)
+                            .chars()
+                            .nth(1)
+                            .is_some_and(|c| c.is_numeric());
+
+                        if next_is_digit {
+                            number.push_str(&self.input.read(1)); // consume the '.'
+                            ty = TokenType::FloatLiteral;
+
+                            while self.input.has_remaining_input()
+                                && self.input.peek().unwrap().is_numeric()
+                            {
+                                number.push_str(&self.input.read(1));
+                            }
+
+                            has_trailing_dot = self.input.peek() == Some('.');
+                        } else {
+                            has_trailing_dot = true;
+                        }
+                    }
+
+                    // The literal's own span must end here, before any
+                    // stray trailing dot(s) are consumed for a separate
+                    // MalformedNumber error below.
+                    self.add_token(ty, number, start);
+
+                    if has_trailing_dot {
+                        let span = self.consume_malformed_number_dots();
+                        let err = LexError::MalformedNumber(span);
+                        self.add_error_token(&err);
+                        self.errors.push(err);
                     }
                 }
+
+                if current.is_alphabetic() {
+                    self.lex_identifier(start);
+                } else if !current.is_numeric() {
+                    // Not a token we recognise; skip it silently, same as
+                    // whitespace.
+                    self.input.read(1);
+                }
             }
+        }
+    }
 
-            self.input.read(1);
+    /// Hands out exactly one token, advancing `Input` only when no lexed
+    /// token is still waiting, so callers (a REPL, a parser) can pull
+    /// tokens lazily instead of waiting for the whole file.
+    /// Yields `Err` for a recoverable lex error instead of its companion
+    /// `Error` token; lexing itself still continues past it, so the next
+    /// call picks back up with whatever comes after.
+    pub fn next_token(&mut self) -> Result<Option<Token>, LexError> {
+        while self.next_index >= self.tokens.len() {
+            if self.done {
+                return Ok(None);
+            }
+
+            self.lex_step();
         }
 
-        // if we cannot find an eol (debug) marker, error
-        if let Some(last_token) = self.tokens.iter().next_back()
-            && !(last_token.ty == TokenType::Eol || last_token.ty == TokenType::EolDebug)
-        {
-            return Err(LexError::UnterminatedLine(self.input.current_location()));
+        let token = self.tokens[self.next_index].clone();
+        self.next_index += 1;
+
+        if token.ty == TokenType::Error {
+            let err = self.errors[self.next_error_index].clone();
+            self.next_error_index += 1;
+
+            return Err(err);
         }
 
-        Ok(self.tokens)
+        Ok(Some(token))
+    }
+
+    pub fn lex(mut self) -> (Vec<Token>, Vec<LexError>) {
+        while !matches!(self.next_token(), Ok(None)) {}
+
+        (self.tokens, self.errors)
+    }
+}
+
+impl Iterator for Lexer {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
     }
 }
 
-pub fn lex(input: Input) -> Result<Vec<Token>, LexError> {
+pub fn lex(input: Input) -> (Vec<Token>, Vec<LexError>) {
     Lexer::new(input).lex()
 }
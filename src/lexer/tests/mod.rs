@@ -1,19 +1,20 @@
+use crate::token::{Location, TokenType};
+
 use super::{Input, lex};
 
 fn test(input: String, expected: String) {
-    let result = lex(Input::of(input));
+    let (tokens, errors) = lex(Input::of(input));
 
     if expected.starts_with("<error(") {
         let error = &expected["<error(".len()..expected.len() - ")>\n".len()];
 
-        assert!(result.is_err());
-        let err = result.err().unwrap();
-        assert_eq!(&err.name(), error);
+        assert!(!errors.is_empty());
+        assert_eq!(&errors[0].name(), error);
 
         return;
     }
 
-    let tokens = result.unwrap();
+    assert!(errors.is_empty());
 
     for (idx, token) in tokens.iter().enumerate() {
         let line = expected.lines().nth(idx);
@@ -40,3 +41,72 @@ fn test_2() {
 fn test_3() {
     test(include_str!("3.berd").to_string(), include_str!("3.ans").to_string())
 }
+
+#[test]
+fn test_4() {
+    test(include_str!("4.berd").to_string(), include_str!("4.ans").to_string())
+}
+
+#[test]
+fn test_5() {
+    test(include_str!("5.berd").to_string(), include_str!("5.ans").to_string())
+}
+
+#[test]
+fn test_6() {
+    test(include_str!("6.berd").to_string(), include_str!("6.ans").to_string())
+}
+
+#[test]
+fn test_7() {
+    test(include_str!("7.berd").to_string(), include_str!("7.ans").to_string())
+}
+
+#[test]
+fn test_spans() {
+    let (tokens, errors) = lex(Input::of("(a) \"hi\" !\n".to_string()));
+
+    assert!(errors.is_empty());
+
+    let spans: Vec<_> = tokens.iter().map(|t| (t.span.start, t.span.end)).collect();
+
+    assert_eq!(
+        spans,
+        vec![
+            (Location { line: 1, col: 1 }, Location { line: 1, col: 2 }), // (
+            (Location { line: 1, col: 2 }, Location { line: 1, col: 3 }), // a
+            (Location { line: 1, col: 3 }, Location { line: 1, col: 4 }), // )
+            (Location { line: 1, col: 5 }, Location { line: 1, col: 9 }), // "hi"
+            (Location { line: 1, col: 10 }, Location { line: 2, col: 1 }), // !
+        ]
+    );
+}
+
+#[test]
+fn test_spans_recovery() {
+    // The `Eol` starts before the dead code that follows it on the same
+    // line, so its span must end there too, not bleed into the junk a
+    // separate `DeadCode` error already covers.
+    let (tokens, errors) = lex(Input::of("! junk\nfoo !\n".to_string()));
+
+    assert_eq!(tokens[0].ty, TokenType::Eol);
+    assert_eq!(
+        (tokens[0].span.start, tokens[0].span.end),
+        (Location { line: 1, col: 1 }, Location { line: 1, col: 3 })
+    );
+    assert_eq!(tokens[1].ty, TokenType::Error);
+    assert_eq!(errors[0].name(), "DeadCode");
+
+    // A `FloatLiteral` followed by a stray extra `.` must keep its own
+    // span tight, with the `MalformedNumber` error coming after it both
+    // in the tokens vec and in the source.
+    let (tokens, errors) = lex(Input::of("3.1.4 !\n".to_string()));
+
+    assert_eq!(tokens[0].ty, TokenType::FloatLiteral);
+    assert_eq!(
+        (tokens[0].span.start, tokens[0].span.end),
+        (Location { line: 1, col: 1 }, Location { line: 1, col: 4 })
+    );
+    assert_eq!(tokens[1].ty, TokenType::Error);
+    assert_eq!(errors[0].name(), "MalformedNumber");
+}
@@ -10,14 +10,17 @@ pub enum TokenType {
     Lbrace,
     Rbrace,
     StringLiteral,
+    CharLiteral,
     Eol,
     EolDebug,
     IntLiteral,
+    FloatLiteral,
     Primitive,
     Comma,
     BoolLiteral,
     Return,
     Not,
+    Error,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -32,11 +35,25 @@ impl Display for Location {
     }
 }
 
+/// A region of source, as line/column `Location`s, from where a token or error begins to
+/// where it ends, so tooling can underline more than a single point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", self.start, self.end)
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Token {
     pub ty: TokenType,
     pub value: String,
-    pub location: Location,
+    pub span: Span,
 }
 
 impl Debug for Token {
@@ -50,14 +67,17 @@ impl Debug for Token {
             TokenType::Lbrace => write!(f, "Lbrace"),
             TokenType::Rbrace => write!(f, "Rbrace"),
             TokenType::StringLiteral => write!(f, "StringLiteral({:?})", self.value.clone()),
+            TokenType::CharLiteral => write!(f, "CharLiteral({:?})", self.value.clone()),
             TokenType::Eol => write!(f, "Eol"),
             TokenType::EolDebug => write!(f, "EolDebug"),
             TokenType::IntLiteral => write!(f, "IntLiteral({})", self.value.clone()),
+            TokenType::FloatLiteral => write!(f, "FloatLiteral({})", self.value.clone()),
             TokenType::Primitive => write!(f, "Primitive({})", self.value.clone()),
             TokenType::Comma => write!(f, "Comma"),
             TokenType::BoolLiteral => write!(f, "BoolLiteral({})", self.value.clone()),
             TokenType::Return => write!(f, "Return"),
             TokenType::Not => write!(f, "Not"),
+            TokenType::Error => write!(f, "Error({})", self.value.clone()),
         }
     }
 }